@@ -16,9 +16,22 @@
  */
 use crate::messages::validator::Message;
 use crate::messages::validator::Message_MessageType;
-use std::sync::mpsc::Receiver;
-use std::sync::mpsc::RecvError;
+use crossbeam_channel::Receiver;
+use crossbeam_channel::Sender;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Wake;
+use std::task::Waker;
+use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 
 /// A Message Sender
 ///
@@ -44,31 +57,111 @@ pub trait MessageSender {
 /// Result for a message received.
 pub type MessageResult = Result<Message, ReceiveError>;
 
-/// A message Receiver
+/// A message Receiver.
+///
+/// This is a multi-consumer channel rather than `std::sync::mpsc::Receiver`,
+/// so it can be cloned and handed out to a pool of worker threads that each
+/// pull and dispatch inbound messages concurrently, instead of forcing every
+/// message onto a single consumer thread. Correlated replies are routed
+/// directly to the waiting `MessageFuture` by a [`MessageRouter`] and never
+/// land on this receiver; see [`inbound_channel`] for constructing the
+/// matching sender/receiver pair.
 pub type MessageReceiver = Receiver<MessageResult>;
 
 /// A Message Connection
 ///
 /// This denotes a connection which can create a MessageSender/Receiver pair.
+/// A `create` implementation is expected to build its `MessageReceiver` from
+/// [`inbound_channel`] and drive inbound traffic through a [`MessageRouter`]
+/// built from the matching sender, so correlated replies reach their
+/// `MessageFuture` and everything else lands on the returned
+/// `MessageReceiver`. That receiver may be cloned, e.g. to spread dispatch
+/// of the unrouted traffic across a pool of worker threads with
+/// [`spawn_dispatch_pool`].
 pub trait MessageConnection<MS: MessageSender> {
     fn create(&self) -> (MS, MessageReceiver);
 }
 
+/// The message that could not be delivered, returned by a failed
+/// [`MessageSender::send`]/[`MessageSender::reply`] so the caller can retry
+/// without re-serializing its contents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndeliveredMessage {
+    destination: Message_MessageType,
+    correlation_id: String,
+    contents: Vec<u8>,
+}
+
+impl UndeliveredMessage {
+    pub fn new(
+        destination: Message_MessageType,
+        correlation_id: String,
+        contents: Vec<u8>,
+    ) -> Self {
+        UndeliveredMessage {
+            destination,
+            correlation_id,
+            contents,
+        }
+    }
+
+    pub fn destination(&self) -> Message_MessageType {
+        self.destination
+    }
+
+    pub fn correlation_id(&self) -> &str {
+        &self.correlation_id
+    }
+
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
+
+    /// Consumes the message, returning just the bytes that failed to send.
+    pub fn into_contents(self) -> Vec<u8> {
+        self.contents
+    }
+
+    /// Consumes the message, returning its destination, correlation id, and
+    /// contents.
+    pub fn into_parts(self) -> (Message_MessageType, String, Vec<u8>) {
+        (self.destination, self.correlation_id, self.contents)
+    }
+}
+
 /// Errors that occur on sending a message.
 #[derive(Debug)]
 pub enum SendError {
-    DisconnectedError,
-    TimeoutError,
+    DisconnectedError(UndeliveredMessage),
+    TimeoutError(UndeliveredMessage),
     UnknownError(String),
 }
 
+impl SendError {
+    /// Returns the bytes that failed to send, if this error carries an
+    /// undelivered message.
+    pub fn into_contents(self) -> Option<Vec<u8>> {
+        self.into_parts().map(|(_, _, contents)| contents)
+    }
+
+    /// Returns the destination, correlation id, and contents of the
+    /// undelivered message, if this error carries one.
+    pub fn into_parts(self) -> Option<(Message_MessageType, String, Vec<u8>)> {
+        match self {
+            SendError::DisconnectedError(msg) => Some(msg.into_parts()),
+            SendError::TimeoutError(msg) => Some(msg.into_parts()),
+            SendError::UnknownError(_) => None,
+        }
+    }
+}
+
 impl std::error::Error for SendError {}
 
 impl std::fmt::Display for SendError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
-            SendError::DisconnectedError => write!(f, "DisconnectedError"),
-            SendError::TimeoutError => write!(f, "TimeoutError"),
+            SendError::DisconnectedError(_) => write!(f, "DisconnectedError"),
+            SendError::TimeoutError(_) => write!(f, "TimeoutError"),
             SendError::UnknownError(ref e) => write!(f, "UnknownError: {}", e),
         }
     }
@@ -78,39 +171,240 @@ impl std::fmt::Display for SendError {
 #[derive(Debug, Clone)]
 pub enum ReceiveError {
     TimeoutError,
-    ChannelError(RecvError),
     DisconnectedError,
 }
 
-impl std::error::Error for ReceiveError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            ReceiveError::ChannelError(err) => Some(&*err),
-            _ => None,
-        }
-    }
-}
+impl std::error::Error for ReceiveError {}
 
 impl std::fmt::Display for ReceiveError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
             ReceiveError::TimeoutError => write!(f, "TimeoutError"),
-            ReceiveError::ChannelError(ref err) => write!(f, "ChannelError: {}", err),
             ReceiveError::DisconnectedError => write!(f, "DisconnectedError"),
         }
     }
 }
+
+/// Error returned by [`MessageFuture::try_get`] when a reply can't be
+/// returned immediately.
+#[derive(Debug, Clone)]
+pub enum TryRecvError {
+    /// No reply has arrived yet; try again later.
+    Empty,
+    DisconnectedError,
+}
+
+impl std::error::Error for TryRecvError {}
+
+impl std::fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            TryRecvError::Empty => write!(f, "Empty"),
+            TryRecvError::DisconnectedError => write!(f, "DisconnectedError"),
+        }
+    }
+}
+
+/// A waker-aware, single-reply channel used to deliver the response to a
+/// single outstanding request.
+///
+/// `std::sync::mpsc::Receiver` has no way to register a `Waker`, so a
+/// `MessageFuture` can't be polled from an async executor if it is backed by
+/// one. This is a small oneshot in the same spirit as `futures-channel`'s:
+/// the sending side writes the value (or is dropped without writing one) and
+/// either wakes a stored `Waker` or leaves the value for a thread blocked on
+/// the condvar to pick up. Both the blocking `get`/`get_timeout` methods and
+/// `Future::poll` read from the same shared state, so they all observe the
+/// same reply (or the same disconnection).
+pub(crate) mod reply_channel {
+    use std::sync::Arc;
+    use std::sync::Condvar;
+    use std::sync::Mutex;
+    use std::task::Context;
+    use std::task::Poll;
+    use std::task::Waker;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    struct Shared<T> {
+        value: Option<T>,
+        waker: Option<Waker>,
+        disconnected: bool,
+    }
+
+    struct Inner<T> {
+        shared: Mutex<Shared<T>>,
+        condvar: Condvar,
+    }
+
+    pub struct Sender<T> {
+        inner: Arc<Inner<T>>,
+    }
+
+    pub struct Receiver<T> {
+        inner: Arc<Inner<T>>,
+    }
+
+    /// The sending half was dropped without ever calling `send`.
+    pub struct Disconnected;
+
+    pub enum RecvTimeoutError {
+        Timeout,
+        Disconnected,
+    }
+
+    pub enum TryRecvError {
+        Empty,
+        Disconnected,
+    }
+
+    pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+        let inner = Arc::new(Inner {
+            shared: Mutex::new(Shared {
+                value: None,
+                waker: None,
+                disconnected: false,
+            }),
+            condvar: Condvar::new(),
+        });
+
+        (
+            Sender {
+                inner: inner.clone(),
+            },
+            Receiver { inner },
+        )
+    }
+
+    impl<T> Sender<T> {
+        /// Delivers the reply, waking a parked thread or a stored `Waker`.
+        pub fn send(self, value: T) {
+            let mut shared = self.inner.shared.lock().expect("reply channel poisoned");
+            shared.value = Some(value);
+            let waker = shared.waker.take();
+            drop(shared);
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+            self.inner.condvar.notify_one();
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            let mut shared = self.inner.shared.lock().expect("reply channel poisoned");
+            if shared.value.is_some() {
+                return;
+            }
+            shared.disconnected = true;
+            let waker = shared.waker.take();
+            drop(shared);
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+            self.inner.condvar.notify_one();
+        }
+    }
+
+    impl<T: Clone> Receiver<T> {
+        /// Blocks the current thread until a reply arrives.
+        pub fn recv(&self) -> Result<T, Disconnected> {
+            let mut shared = self.inner.shared.lock().expect("reply channel poisoned");
+            loop {
+                if let Some(ref value) = shared.value {
+                    return Ok(value.clone());
+                }
+                if shared.disconnected {
+                    return Err(Disconnected);
+                }
+                shared = self.inner.condvar.wait(shared).expect("reply channel poisoned");
+            }
+        }
+
+        /// Blocks the current thread until a reply arrives or `timeout` elapses.
+        pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+            self.recv_deadline(Instant::now() + timeout)
+        }
+
+        /// Blocks the current thread until a reply arrives or `deadline` passes.
+        ///
+        /// Unlike `recv_timeout`, the deadline is an absolute instant, so a
+        /// caller polling several channels against one shared deadline doesn't
+        /// need to recompute a shrinking `Duration` for each one.
+        pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+            let mut shared = self.inner.shared.lock().expect("reply channel poisoned");
+            loop {
+                if let Some(ref value) = shared.value {
+                    return Ok(value.clone());
+                }
+                if shared.disconnected {
+                    return Err(RecvTimeoutError::Disconnected);
+                }
+
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(RecvTimeoutError::Timeout);
+                }
+
+                let (guard, timed_out) = self
+                    .inner
+                    .condvar
+                    .wait_timeout(shared, deadline - now)
+                    .expect("reply channel poisoned");
+                shared = guard;
+                if timed_out.timed_out() && shared.value.is_none() && !shared.disconnected {
+                    return Err(RecvTimeoutError::Timeout);
+                }
+            }
+        }
+
+        /// Returns the reply immediately if one is ready, without blocking.
+        pub fn try_recv(&self) -> Result<T, TryRecvError> {
+            let shared = self.inner.shared.lock().expect("reply channel poisoned");
+            if let Some(ref value) = shared.value {
+                return Ok(value.clone());
+            }
+            if shared.disconnected {
+                return Err(TryRecvError::Disconnected);
+            }
+            Err(TryRecvError::Empty)
+        }
+
+        /// Registers `cx`'s waker and returns `Poll::Pending` if no reply has
+        /// arrived yet, otherwise returns the reply without blocking.
+        pub fn poll(&self, cx: &mut Context<'_>) -> Poll<Result<T, Disconnected>> {
+            let mut shared = self.inner.shared.lock().expect("reply channel poisoned");
+            if let Some(ref value) = shared.value {
+                return Poll::Ready(Ok(value.clone()));
+            }
+            if shared.disconnected {
+                return Poll::Ready(Err(Disconnected));
+            }
+            shared.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
 /// MessageFuture is a promise for the reply to a sent message on connection.
+///
+/// It can either be driven to completion synchronously via [`MessageFuture::get`]
+/// and [`MessageFuture::get_timeout`], or `.await`ed like any other
+/// `std::future::Future`.
 pub struct MessageFuture {
-    inner: Receiver<MessageResult>,
+    inner: reply_channel::Receiver<MessageResult>,
     result: Option<MessageResult>,
+    cleanup: Option<(Weak<RouterState>, String)>,
 }
 
 impl MessageFuture {
-    pub fn new(inner: Receiver<MessageResult>) -> Self {
+    pub fn new(inner: reply_channel::Receiver<MessageResult>) -> Self {
         MessageFuture {
             inner,
             result: None,
+            cleanup: None,
         }
     }
 
@@ -124,7 +418,7 @@ impl MessageFuture {
                 self.result = Some(result.clone());
                 result
             }
-            Err(err) => Err(ReceiveError::ChannelError(err)),
+            Err(_) => Err(ReceiveError::DisconnectedError),
         }
     }
 
@@ -138,7 +432,8 @@ impl MessageFuture {
                 self.result = Some(result.clone());
                 result
             }
-            Err(_) => Err(ReceiveError::TimeoutError),
+            Err(reply_channel::RecvTimeoutError::Disconnected) => Err(ReceiveError::DisconnectedError),
+            Err(reply_channel::RecvTimeoutError::Timeout) => Err(ReceiveError::TimeoutError),
         }
     }
 
@@ -149,20 +444,423 @@ impl MessageFuture {
             self.get()
         }
     }
+
+    /// Blocks the current thread until a reply arrives or `deadline` passes.
+    ///
+    /// Unlike `get_timeout`, the deadline is an absolute `Instant`, so a
+    /// caller polling several `MessageFuture`s against a single shared
+    /// deadline doesn't need to recompute a shrinking `Duration` for each one.
+    pub fn get_deadline(&mut self, deadline: Instant) -> MessageResult {
+        if let Some(ref result) = self.result {
+            return result.clone();
+        }
+
+        match self.inner.recv_deadline(deadline) {
+            Ok(result) => {
+                self.result = Some(result.clone());
+                result
+            }
+            Err(reply_channel::RecvTimeoutError::Disconnected) => {
+                Err(ReceiveError::DisconnectedError)
+            }
+            Err(reply_channel::RecvTimeoutError::Timeout) => Err(ReceiveError::TimeoutError),
+        }
+    }
+
+    /// Returns the reply immediately if one is ready, without blocking.
+    pub fn try_get(&mut self) -> Result<MessageResult, TryRecvError> {
+        if let Some(ref result) = self.result {
+            return Ok(result.clone());
+        }
+
+        match self.inner.try_recv() {
+            Ok(result) => {
+                self.result = Some(result.clone());
+                Ok(result)
+            }
+            Err(reply_channel::TryRecvError::Empty) => Err(TryRecvError::Empty),
+            Err(reply_channel::TryRecvError::Disconnected) => Err(TryRecvError::DisconnectedError),
+        }
+    }
 }
 
-/// Queue for inbound messages, sent directly to this stream.
+impl Future for MessageFuture {
+    type Output = MessageResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Some(ref result) = this.result {
+            return Poll::Ready(result.clone());
+        }
+
+        match this.inner.poll(cx) {
+            Poll::Ready(Ok(result)) => {
+                this.result = Some(result.clone());
+                Poll::Ready(result)
+            }
+            Poll::Ready(Err(_)) => {
+                let result = Err(ReceiveError::DisconnectedError);
+                this.result = Some(result.clone());
+                Poll::Ready(result)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Removes a [`MessageRouter`]'s pending entry once the `MessageFuture`
+/// waiting on it goes away, so a caller that times out or simply drops its
+/// future doesn't leak the router's `HashMap` entry (and the
+/// `reply_channel::Sender` it holds) forever.
+impl Drop for MessageFuture {
+    fn drop(&mut self) {
+        if let Some((router, correlation_id)) = self.cleanup.take() {
+            if let Some(router) = router.upgrade() {
+                router
+                    .pending
+                    .lock()
+                    .expect("router poisoned")
+                    .remove(&correlation_id);
+            }
+        }
+    }
+}
+
+/// A `Waker` that does nothing when woken, for polling a future without
+/// registering any real interest in being notified again.
+fn noop_waker() -> Waker {
+    use std::task::RawWaker;
+    use std::task::RawWakerVTable;
+
+    fn clone(_: *const ()) -> RawWaker {
+        raw_waker()
+    }
+    fn no_op(_: *const ()) {}
+
+    fn raw_waker() -> RawWaker {
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    unsafe { Waker::from_raw(raw_waker()) }
+}
+
+/// Shared wake signal used by [`MessageSelect`] to block until any one of
+/// several registered `MessageFuture`s becomes ready.
+struct SelectSignal {
+    ready: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl SelectSignal {
+    fn new() -> Arc<Self> {
+        Arc::new(SelectSignal {
+            ready: Mutex::new(false),
+            condvar: Condvar::new(),
+        })
+    }
+
+    fn notify(&self) {
+        let mut ready = self.ready.lock().expect("select signal poisoned");
+        *ready = true;
+        self.condvar.notify_all();
+    }
+
+    /// Blocks until `notify` is called at least once since the last wait.
+    fn wait(&self) {
+        let mut ready = self.ready.lock().expect("select signal poisoned");
+        while !*ready {
+            ready = self.condvar.wait(ready).expect("select signal poisoned");
+        }
+        *ready = false;
+    }
+
+    /// Like `wait`, but gives up once `deadline` passes. Returns whether it
+    /// was notified.
+    fn wait_deadline(&self, deadline: Instant) -> bool {
+        let mut ready = self.ready.lock().expect("select signal poisoned");
+        loop {
+            if *ready {
+                *ready = false;
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            let (guard, _) = self
+                .condvar
+                .wait_timeout(ready, deadline - now)
+                .expect("select signal poisoned");
+            ready = guard;
+        }
+    }
+}
+
+impl Wake for SelectSignal {
+    fn wake(self: Arc<Self>) {
+        self.notify();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notify();
+    }
+}
+
+/// Waits for the first reply among several outstanding [`MessageFuture`]s,
+/// so a client that has issued several requests concurrently doesn't have to
+/// poll each one in a loop.
+///
+/// Registered futures keep their position: the index returned by `select`
+/// and its variants is the index returned by [`MessageSelect::register`].
+pub struct MessageSelect<'a> {
+    futures: Vec<&'a mut MessageFuture>,
+}
+
+impl<'a> MessageSelect<'a> {
+    pub fn new() -> Self {
+        MessageSelect {
+            futures: Vec::new(),
+        }
+    }
+
+    /// Registers a `MessageFuture` to be selected over, returning its index.
+    pub fn register(&mut self, future: &'a mut MessageFuture) -> usize {
+        self.futures.push(future);
+        self.futures.len() - 1
+    }
+
+    fn poll_all(&mut self, cx: &mut Context<'_>) -> Option<(usize, MessageResult)> {
+        for (index, future) in self.futures.iter_mut().enumerate() {
+            if let Poll::Ready(result) = Pin::new(&mut **future).poll(cx) {
+                return Some((index, result));
+            }
+        }
+        None
+    }
+
+    /// Returns the first ready reply, without blocking.
+    pub fn try_select(mut self) -> Option<(usize, MessageResult)> {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        self.poll_all(&mut cx)
+    }
+
+    /// Blocks the current thread until any registered future has a reply.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no futures have been registered, since there would be
+    /// nothing that could ever wake this call up (mirroring
+    /// `crossbeam_channel::Select`, which panics on an empty operation set
+    /// for the same reason).
+    pub fn select(mut self) -> (usize, MessageResult) {
+        assert!(
+            !self.futures.is_empty(),
+            "MessageSelect::select called with no registered futures"
+        );
+
+        let signal = SelectSignal::new();
+        let waker = Waker::from(signal.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if let Some(found) = self.poll_all(&mut cx) {
+                return found;
+            }
+            signal.wait();
+        }
+    }
+
+    /// Blocks until any registered future has a reply or `timeout` elapses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no futures have been registered; see [`MessageSelect::select`].
+    pub fn select_timeout(self, timeout: Duration) -> Option<(usize, MessageResult)> {
+        self.select_deadline(Instant::now() + timeout)
+    }
+
+    /// Blocks until any registered future has a reply or `deadline` passes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no futures have been registered; see [`MessageSelect::select`].
+    pub fn select_deadline(mut self, deadline: Instant) -> Option<(usize, MessageResult)> {
+        assert!(
+            !self.futures.is_empty(),
+            "MessageSelect::select_deadline called with no registered futures"
+        );
+
+        let signal = SelectSignal::new();
+        let waker = Waker::from(signal.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if let Some(found) = self.poll_all(&mut cx) {
+                return Some(found);
+            }
+            if Instant::now() >= deadline || !signal.wait_deadline(deadline) {
+                // One last poll in case a reply raced the deadline.
+                return self.poll_all(&mut cx);
+            }
+        }
+    }
+}
+
+impl<'a> Default for MessageSelect<'a> {
+    fn default() -> Self {
+        MessageSelect::new()
+    }
+}
+
+/// Creates the channel pair behind a `MessageConnection`'s inbound queue.
+///
+/// The `Sender` half is given to a [`MessageRouter`]; the `Receiver` half is
+/// the [`MessageReceiver`] a `MessageConnection::create` implementation
+/// returns (and that [`spawn_dispatch_pool`] drains from).
+pub fn inbound_channel() -> (Sender<MessageResult>, MessageReceiver) {
+    crossbeam_channel::unbounded()
+}
+
+struct RouterState {
+    pending: Mutex<HashMap<String, reply_channel::Sender<MessageResult>>>,
+    unrouted: Sender<MessageResult>,
+}
+
+/// Splits inbound traffic between outstanding requests and the shared
+/// dispatch queue.
+///
+/// A `MessageConnection` implementation owns one `MessageRouter`, built from
+/// the sending half of an [`inbound_channel`] pair, alongside its transport's
+/// read loop. For every inbound message it looks up the originating
+/// correlation id: if a caller is waiting on it (via [`MessageRouter::register`]),
+/// the message is delivered straight to that request's `MessageFuture`
+/// through its reply channel; otherwise it is pushed onto the shared MPMC
+/// queue for [`spawn_dispatch_pool`]'s workers to pick up. This is what makes
+/// the multi-consumer `MessageReceiver` safe to share: replies a
+/// `MessageFuture` is waiting on can never be stolen by a dispatch worker.
+///
+/// The router's state lives behind an `Arc` so a registered `MessageFuture`
+/// can hold a `Weak` back-reference to it: if that future is dropped or times
+/// out before a reply arrives, its `Drop` impl removes the now-useless
+/// `pending` entry instead of leaking it for the lifetime of the router.
+pub struct MessageRouter {
+    state: Arc<RouterState>,
+}
+
+impl MessageRouter {
+    /// Creates a router that forwards messages with no registered
+    /// correlation id onto `unrouted`, the sending half of an
+    /// [`inbound_channel`] pair.
+    pub fn new(unrouted: Sender<MessageResult>) -> Self {
+        MessageRouter {
+            state: Arc::new(RouterState {
+                pending: Mutex::new(HashMap::new()),
+                unrouted,
+            }),
+        }
+    }
+
+    /// Registers interest in the reply to `correlation_id`, returning the
+    /// `MessageFuture` that `route` resolves once it arrives.
+    ///
+    /// If the returned `MessageFuture` is dropped without ever being routed
+    /// (the caller gave up, timed out, or simply lost interest), its `Drop`
+    /// impl removes this entry so it doesn't linger in `pending` forever.
+    pub fn register(&self, correlation_id: impl Into<String>) -> MessageFuture {
+        let correlation_id = correlation_id.into();
+        let (sender, receiver) = reply_channel::channel();
+        self.state
+            .pending
+            .lock()
+            .expect("router poisoned")
+            .insert(correlation_id.clone(), sender);
+
+        let mut future = MessageFuture::new(receiver);
+        future.cleanup = Some((Arc::downgrade(&self.state), correlation_id));
+        future
+    }
+
+    /// Delivers an inbound message to the `MessageFuture` registered for
+    /// `correlation_id`, or onto the shared dispatch queue if nothing is
+    /// waiting on it.
+    pub fn route(&self, correlation_id: &str, message: MessageResult) {
+        let sender = self
+            .state
+            .pending
+            .lock()
+            .expect("router poisoned")
+            .remove(correlation_id);
+
+        match sender {
+            Some(sender) => sender.send(message),
+            None => {
+                // Nobody is waiting on this one (an unsolicited message, or a
+                // reply whose caller already gave up); hand it to the shared
+                // dispatch queue instead of dropping it.
+                let _ = self.state.unrouted.send(message);
+            }
+        }
+    }
+
+    /// Returns the number of correlation ids still awaiting a reply.
+    #[cfg(test)]
+    fn pending_count(&self) -> usize {
+        self.state.pending.lock().expect("router poisoned").len()
+    }
+}
+
+/// Spawns a pool of `worker_count` threads that each pull from the shared
+/// inbound `receiver` and hand every message to `handler`, so CPU-bound
+/// dispatch work can be spread across threads instead of serialized onto a
+/// single consumer.
+///
+/// `receiver` should only carry messages a [`MessageRouter`] couldn't
+/// correlate to an outstanding request; correlated replies are delivered to
+/// their `MessageFuture`'s reply channel instead. Returns the worker
+/// `JoinHandle`s so the caller can wait for them to drain after closing the
+/// connection.
+pub fn spawn_dispatch_pool<F>(
+    receiver: MessageReceiver,
+    worker_count: usize,
+    handler: F,
+) -> Vec<thread::JoinHandle<()>>
+where
+    F: Fn(MessageResult) + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    (0..worker_count)
+        .map(|_| {
+            let receiver = receiver.clone();
+            let handler = Arc::clone(&handler);
+            thread::spawn(move || {
+                while let Ok(message) = receiver.recv() {
+                    handler(message);
+                }
+            })
+        })
+        .collect()
+}
 
 #[cfg(test)]
 mod tests {
 
-    use std::sync::mpsc::channel;
     use std::thread;
 
     use crate::messages::validator::Message;
     use crate::messages::validator::Message_MessageType;
 
+    use super::inbound_channel;
+    use super::noop_waker;
+    use super::reply_channel;
+    use super::spawn_dispatch_pool;
     use super::MessageFuture;
+    use super::MessageRouter;
+    use super::MessageSelect;
+    use super::SendError;
+    use super::UndeliveredMessage;
 
     fn make_ping(correlation_id: &str) -> Message {
         let mut message = Message::new();
@@ -175,12 +873,12 @@ mod tests {
 
     #[test]
     fn future_get() {
-        let (tx, rx) = channel();
+        let (tx, rx) = reply_channel::channel();
 
         let mut fut = MessageFuture::new(rx);
 
         let t = thread::spawn(move || {
-            tx.send(Ok(make_ping("my_test"))).unwrap();
+            tx.send(Ok(make_ping("my_test")));
         });
 
         let msg = fut.get().expect("Should have a message");
@@ -189,4 +887,351 @@ mod tests {
 
         assert_eq!(msg, make_ping("my_test"));
     }
+
+    #[test]
+    fn get_timeout_returns_timeout_error_when_no_reply_arrives() {
+        use std::time::Duration;
+
+        let (tx, rx) = reply_channel::channel();
+        let mut fut = MessageFuture::new(rx);
+
+        let err = fut
+            .get_timeout(Duration::from_millis(10))
+            .expect_err("Should not have a message");
+        assert!(matches!(err, super::ReceiveError::TimeoutError));
+
+        // The sender is still alive, so a later call can still succeed.
+        tx.send(Ok(make_ping("my_test")));
+        assert_eq!(
+            fut.get_timeout(Duration::from_secs(1))
+                .expect("Should have a message"),
+            make_ping("my_test")
+        );
+    }
+
+    #[test]
+    fn get_timeout_returns_disconnected_error_when_sender_dropped() {
+        use std::time::Duration;
+
+        let (tx, rx) = reply_channel::channel();
+        let mut fut = MessageFuture::new(rx);
+        drop(tx);
+
+        let err = fut
+            .get_timeout(Duration::from_millis(10))
+            .expect_err("Should not have a message");
+        assert!(matches!(err, super::ReceiveError::DisconnectedError));
+    }
+
+    #[test]
+    fn get_deadline_returns_reply_before_deadline() {
+        use std::time::Instant;
+
+        let (tx, rx) = reply_channel::channel();
+        let mut fut = MessageFuture::new(rx);
+
+        let t = thread::spawn(move || {
+            tx.send(Ok(make_ping("my_test")));
+        });
+
+        let msg = fut
+            .get_deadline(Instant::now() + std::time::Duration::from_secs(1))
+            .expect("Should have a message");
+
+        t.join().unwrap();
+
+        assert_eq!(msg, make_ping("my_test"));
+    }
+
+    #[test]
+    fn get_deadline_times_out_when_deadline_passes() {
+        use std::time::Instant;
+
+        let (_tx, rx) = reply_channel::channel();
+        let mut fut = MessageFuture::new(rx);
+
+        let err = fut
+            .get_deadline(Instant::now())
+            .expect_err("Should not have a message");
+        assert!(matches!(err, super::ReceiveError::TimeoutError));
+    }
+
+    #[test]
+    fn try_get_returns_empty_until_reply_arrives() {
+        let (tx, rx) = reply_channel::channel();
+        let mut fut = MessageFuture::new(rx);
+
+        assert!(matches!(
+            fut.try_get().expect_err("Should not have a message yet"),
+            super::TryRecvError::Empty
+        ));
+
+        tx.send(Ok(make_ping("my_test")));
+
+        let msg = fut.try_get().expect("Should have a message").expect("Should be Ok");
+        assert_eq!(msg, make_ping("my_test"));
+    }
+
+    #[test]
+    fn try_get_returns_disconnected_when_sender_dropped() {
+        let (tx, rx) = reply_channel::channel();
+        let mut fut = MessageFuture::new(rx);
+        drop(tx);
+
+        assert!(matches!(
+            fut.try_get().expect_err("Should not have a message"),
+            super::TryRecvError::DisconnectedError
+        ));
+    }
+
+    #[test]
+    fn future_await() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::Context;
+        use std::task::Poll;
+
+        let (tx, rx) = reply_channel::channel();
+
+        let mut fut = MessageFuture::new(rx);
+
+        let t = thread::spawn(move || {
+            tx.send(Ok(make_ping("my_test")));
+        });
+
+        // A minimal spin-and-poll "executor": there is no running reactor
+        // here, so just spin until the reply is delivered. A real async
+        // caller would drive this via `.await` on a real executor instead.
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let msg = loop {
+            match Pin::new(&mut fut).poll(&mut cx) {
+                Poll::Ready(result) => break result.expect("Should have a message"),
+                Poll::Pending => thread::yield_now(),
+            }
+        };
+
+        t.join().unwrap();
+
+        assert_eq!(msg, make_ping("my_test"));
+    }
+
+    #[test]
+    fn select_returns_first_ready() {
+        let (tx1, rx1) = reply_channel::channel();
+        let (tx2, rx2) = reply_channel::channel();
+
+        let mut fut1 = MessageFuture::new(rx1);
+        let mut fut2 = MessageFuture::new(rx2);
+
+        let t = thread::spawn(move || {
+            tx2.send(Ok(make_ping("my_test")));
+        });
+
+        let mut select = MessageSelect::new();
+        select.register(&mut fut1);
+        let second_index = select.register(&mut fut2);
+
+        let (index, result) = select.select();
+
+        t.join().unwrap();
+        drop(tx1);
+
+        assert_eq!(index, second_index);
+        assert_eq!(result.expect("Should have a message"), make_ping("my_test"));
+    }
+
+    #[test]
+    fn try_select_returns_none_until_a_reply_arrives() {
+        let (tx1, rx1) = reply_channel::channel();
+        let (tx2, rx2) = reply_channel::channel();
+
+        let mut fut1 = MessageFuture::new(rx1);
+        let mut fut2 = MessageFuture::new(rx2);
+
+        let mut select = MessageSelect::new();
+        select.register(&mut fut1);
+        select.register(&mut fut2);
+        assert!(select.try_select().is_none());
+
+        tx2.send(Ok(make_ping("my_test")));
+
+        let mut select = MessageSelect::new();
+        let first_index = select.register(&mut fut1);
+        let second_index = select.register(&mut fut2);
+
+        let (index, result) = select.try_select().expect("Should have a reply ready");
+
+        drop(tx1);
+
+        assert_eq!(index, second_index);
+        assert_ne!(index, first_index);
+        assert_eq!(result.expect("Should have a message"), make_ping("my_test"));
+    }
+
+    #[test]
+    fn select_timeout_returns_none_when_nothing_replies() {
+        let (tx1, rx1) = reply_channel::channel();
+        let (tx2, rx2) = reply_channel::channel();
+
+        let mut fut1 = MessageFuture::new(rx1);
+        let mut fut2 = MessageFuture::new(rx2);
+
+        let mut select = MessageSelect::new();
+        select.register(&mut fut1);
+        select.register(&mut fut2);
+
+        assert!(select
+            .select_timeout(std::time::Duration::from_millis(10))
+            .is_none());
+
+        drop(tx1);
+        drop(tx2);
+    }
+
+    #[test]
+    fn select_deadline_returns_reply_before_deadline() {
+        let (tx1, rx1) = reply_channel::channel();
+        let (tx2, rx2) = reply_channel::channel();
+
+        let mut fut1 = MessageFuture::new(rx1);
+        let mut fut2 = MessageFuture::new(rx2);
+
+        let t = thread::spawn(move || {
+            tx1.send(Ok(make_ping("my_test")));
+        });
+
+        let mut select = MessageSelect::new();
+        let first_index = select.register(&mut fut1);
+        select.register(&mut fut2);
+
+        let (index, result) = select
+            .select_deadline(std::time::Instant::now() + std::time::Duration::from_secs(1))
+            .expect("Should have a reply");
+
+        t.join().unwrap();
+        drop(tx2);
+
+        assert_eq!(index, first_index);
+        assert_eq!(result.expect("Should have a message"), make_ping("my_test"));
+    }
+
+    #[test]
+    #[should_panic(expected = "no registered futures")]
+    fn select_panics_with_no_registered_futures() {
+        let _ = MessageSelect::new().select();
+    }
+
+    #[test]
+    #[should_panic(expected = "no registered futures")]
+    fn select_deadline_panics_with_no_registered_futures() {
+        let _ = MessageSelect::new().select_deadline(std::time::Instant::now());
+    }
+
+    #[test]
+    #[should_panic(expected = "no registered futures")]
+    fn select_timeout_panics_with_no_registered_futures() {
+        let _ = MessageSelect::new().select_timeout(std::time::Duration::from_millis(10));
+    }
+
+    #[test]
+    fn try_select_returns_none_with_no_registered_futures() {
+        assert!(MessageSelect::new().try_select().is_none());
+    }
+
+    #[test]
+    fn send_error_recovers_undelivered_contents() {
+        let undelivered = UndeliveredMessage::new(
+            Message_MessageType::PING_REQUEST,
+            String::from("my_test"),
+            b"PING".to_vec(),
+        );
+
+        let err = SendError::DisconnectedError(undelivered);
+
+        assert_eq!(err.into_contents(), Some(b"PING".to_vec()));
+    }
+
+    #[test]
+    fn dispatch_pool_processes_messages_from_multiple_workers() {
+        use std::sync::mpsc::channel;
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let (done_tx, done_rx) = channel();
+
+        let expected: Vec<Message> = (0..8).map(|i| make_ping(&format!("msg-{}", i))).collect();
+
+        let workers = spawn_dispatch_pool(rx, 4, move |message| {
+            done_tx.send(message).expect("receiver should still be alive");
+        });
+
+        for message in &expected {
+            tx.send(Ok(message.clone()))
+                .expect("workers should still be listening");
+        }
+        drop(tx);
+
+        let mut received: Vec<Message> = (0..expected.len())
+            .map(|_| {
+                done_rx
+                    .recv()
+                    .expect("should receive a dispatched message")
+                    .expect("message should be Ok")
+            })
+            .collect();
+
+        for worker in workers {
+            worker.join().expect("worker thread should not panic");
+        }
+
+        let sort_key = |m: &Message| m.get_correlation_id().to_string();
+        received.sort_by_key(sort_key);
+        let mut expected = expected;
+        expected.sort_by_key(sort_key);
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn router_delivers_correlated_reply_to_its_future() {
+        let (unrouted_tx, unrouted_rx) = inbound_channel();
+        let router = MessageRouter::new(unrouted_tx);
+
+        let mut fut = router.register("my_test");
+        router.route("my_test", Ok(make_ping("my_test")));
+
+        let msg = fut.get().expect("Should have a message");
+        assert_eq!(msg, make_ping("my_test"));
+
+        // Nothing should have been pushed to the shared dispatch queue.
+        assert!(unrouted_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn router_drops_pending_entry_when_future_is_dropped_unrouted() {
+        let (unrouted_tx, _unrouted_rx) = inbound_channel();
+        let router = MessageRouter::new(unrouted_tx);
+
+        let fut = router.register("never_replied");
+        assert_eq!(router.pending_count(), 1);
+
+        drop(fut);
+
+        assert_eq!(router.pending_count(), 0);
+    }
+
+    #[test]
+    fn router_forwards_unmatched_reply_to_dispatch_queue() {
+        let (unrouted_tx, unrouted_rx) = inbound_channel();
+        let router = MessageRouter::new(unrouted_tx);
+
+        // No one registered interest in "unknown", so it should land on the
+        // shared queue for a dispatch worker to pick up instead.
+        router.route("unknown", Ok(make_ping("unknown")));
+
+        let msg = unrouted_rx
+            .recv()
+            .expect("should receive the unrouted message")
+            .expect("message should be Ok");
+        assert_eq!(msg, make_ping("unknown"));
+    }
 }